@@ -4,6 +4,7 @@ use once_cell::sync::Lazy;
 use std::{
     borrow::Cow,
     collections::HashMap,
+    path::PathBuf,
     sync::{Arc, Mutex},
     time::Duration,
 };
@@ -13,12 +14,13 @@ use ureq::Agent;
 use helix_view::{
     codestats::CodeStatsEvent,
     events::{DocumentDidChange, Quit},
+    handlers::change::TextChange,
     Document,
 };
 
 use crate::{compositor, config::Config as GlobalConfig, events::PostInsertChar, ui::PromptEvent};
 use arc_swap::ArcSwap;
-use helix_event::{register_hook, send_blocking, send_blocking_freezing, CancelTx};
+use helix_event::{register_hook, send_blocking_freezing, CancelTx};
 use helix_view::handlers::Handlers;
 use serde::{Deserialize, Serialize};
 
@@ -45,6 +47,10 @@ pub(super) struct CodeStatsHandler {
     config: Arc<ArcSwap<GlobalConfig>>,
     agent: Agent,
     last_send: DateTime<Local>,
+    /// Pulses written to the on-disk journal but not yet confirmed sent.
+    /// Kept around (and re-attempted) across network failures and, via
+    /// the journal, across restarts.
+    unsent: Vec<CodeStatsPulse>,
 }
 
 impl CodeStatsHandler {
@@ -57,29 +63,132 @@ impl CodeStatsHandler {
             .build();
 
         let local_time = Local::now();
+        let unsent = read_journal();
+        if !unsent.is_empty() {
+            log::info!(
+                "Found {} queued CodeStats pulse(s) left over from a previous session, \
+                 will retry sending them once the handler starts",
+                unsent.len()
+            );
+        }
 
         CodeStatsHandler {
             trigger: None,
             request: None,
-            config: config,
-            agent: agent,
+            config,
+            agent,
             last_send: local_time,
+            unsent,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CodeStatsPulseXP {
     language: String,
     xp: u32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CodeStatsPulse {
     coded_at: String, // or dt
     xps: Vec<CodeStatsPulseXP>,
 }
 
+/// File name of the CodeStats journal inside the Helix cache dir.
+const JOURNAL_FILE_NAME: &str = "codestats_journal.jsonl";
+/// Drop the oldest queued pulses past this count instead of growing the
+/// journal unboundedly if the server is unreachable for a long time.
+const JOURNAL_MAX_PULSES: usize = 500;
+
+fn journal_path() -> PathBuf {
+    helix_loader::cache_dir().join(JOURNAL_FILE_NAME)
+}
+
+/// Read back any pulses left in the journal by a previous run.
+fn read_journal() -> Vec<CodeStatsPulse> {
+    let Ok(contents) = std::fs::read_to_string(journal_path()) else {
+        return Vec::new();
+    };
+
+    contents
+        .lines()
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(pulse) => Some(pulse),
+            Err(err) => {
+                log::warn!("Skipping corrupt CodeStats journal line: {err:#?}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Drop the oldest pulses in `pulses` until at most `max` remain, so an
+/// unreachable server can't grow the journal forever.
+fn cap_pulses(pulses: &mut Vec<CodeStatsPulse>, max: usize) {
+    if pulses.len() > max {
+        let drop_count = pulses.len() - max;
+        log::warn!("CodeStats journal exceeded {max} queued pulse(s), dropping {drop_count} oldest");
+        pulses.drain(..drop_count);
+    }
+}
+
+/// Persist `pulses` to the journal as newline-delimited JSON, capping the
+/// number of queued pulses so an unreachable server can't grow the journal
+/// forever.
+fn write_journal(pulses: &mut Vec<CodeStatsPulse>) {
+    cap_pulses(pulses, JOURNAL_MAX_PULSES);
+
+    let path = journal_path();
+    if let Some(parent) = path.parent() {
+        if let Err(err) = std::fs::create_dir_all(parent) {
+            log::warn!("Failed to create CodeStats cache dir: {err:#?}");
+            return;
+        }
+    }
+
+    let mut data = String::new();
+    for pulse in pulses.iter() {
+        match serde_json::to_string(pulse) {
+            Ok(line) => {
+                data.push_str(&line);
+                data.push('\n');
+            }
+            Err(err) => log::warn!("Failed to serialize CodeStats pulse: {err:#?}"),
+        }
+    }
+
+    if let Err(err) = std::fs::write(&path, data) {
+        log::warn!("Failed to write CodeStats journal: {err:#?}");
+    }
+}
+
+fn clear_journal() {
+    if let Err(err) = std::fs::remove_file(journal_path()) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Failed to clear CodeStats journal: {err:#?}");
+        }
+    }
+}
+
+/// Merge a batch of queued pulses into one, summing XP per language.
+fn coalesce(pulses: &[CodeStatsPulse]) -> CodeStatsPulse {
+    let mut merged: HashMap<String, u32> = HashMap::new();
+    for pulse in pulses {
+        for xp in &pulse.xps {
+            *merged.entry(xp.language.clone()).or_insert(0) += xp.xp;
+        }
+    }
+
+    CodeStatsPulse {
+        coded_at: Local::now().to_rfc3339(),
+        xps: merged
+            .into_iter()
+            .map(|(language, xp)| CodeStatsPulseXP { language, xp })
+            .collect(),
+    }
+}
+
 impl CodeStatsHandler {
     fn should_send(&self, now: &DateTime<Local>) -> bool {
         (*now - self.last_send).num_seconds() > 10
@@ -134,31 +243,32 @@ impl helix_event::AsyncHook for CodeStatsHandler {
     fn finish_debounce(&mut self) {
         let trigger = self.trigger.take().expect("debounce always has a trigger");
 
-        // pull actual config
-        let cfg = &self.config.load_full().codestats;
-
-        // check there a key in config
-        let Some(key) = &cfg.key else {
-            return;
-        };
-
         // copy xps array
-        let local_xps = {
+        let new_xps = {
             let mut xps_ptr = XPS.lock().expect("why i cant lock XPS...");
 
-            // if no data -> return
             if xps_ptr.is_empty() {
-                return;
+                None
+            } else {
+                let xps_copy = xps_ptr.clone();
+                xps_ptr.clear();
+                Some(xps_copy)
             }
-
-            // copy dict
-            let xps_copy = xps_ptr.clone();
-
-            // and clean it
-            xps_ptr.clear();
-            xps_copy
         };
 
+        // queue and journal any freshly earned XP *before* we try to send
+        // anything, so a crash or a failed request doesn't lose it.
+        if let Some(xps) = new_xps {
+            self.unsent.push(CodeStatsPulse {
+                coded_at: Local::now().to_rfc3339(),
+                xps: xps
+                    .into_iter()
+                    .map(|(language, xp)| CodeStatsPulseXP { language, xp })
+                    .collect(),
+            });
+            write_journal(&mut self.unsent);
+        }
+
         let now = Local::now();
 
         // check now, if it should not be sended AND trigger is not ForceSend...
@@ -166,18 +276,31 @@ impl helix_event::AsyncHook for CodeStatsHandler {
             return;
         }
 
-        // build request
-        let pulse = CodeStatsPulse {
-            coded_at: now.to_rfc3339(),
-            xps: local_xps
-                .into_iter()
-                .map(|(lang, xp)| CodeStatsPulseXP {
-                    language: lang,
-                    xp: xp,
-                })
-                .collect(),
+        self.send_unsent();
+    }
+}
+
+impl CodeStatsHandler {
+    /// Attempt to flush `self.unsent` to the server right now, ignoring
+    /// the normal debounce interval. Used for `CodeStatsEvent::ForceSend`
+    /// and to replay pulses left over from a previous session. Pulses are
+    /// only dropped (and the journal pruned) once the server accepts
+    /// them; any failure leaves them queued for the next attempt.
+    fn send_unsent(&mut self) {
+        if self.unsent.is_empty() {
+            return;
+        }
+
+        // pull actual config
+        let cfg = &self.config.load_full().codestats;
+
+        // check there a key in config
+        let Some(key) = &cfg.key else {
+            return;
         };
 
+        let pulse = coalesce(&self.unsent);
+
         // just for debbuging ;)
         let j = serde_json::to_string(&pulse).unwrap();
 
@@ -187,18 +310,22 @@ impl helix_event::AsyncHook for CodeStatsHandler {
             .agent
             .post(&path)
             .set("X-API-Token", key)
-            .send_json(pulse);
+            .send_json(&pulse);
 
         match resp {
             Ok(x) => match x.into_string() {
-                Ok(data) => log::info!("Sended {j:#?} ok: {data:#?}"),
+                Ok(data) => {
+                    log::info!("Sended {j:#?} ok: {data:#?}");
+                    self.unsent.clear();
+                    clear_journal();
+                }
                 Err(x) => log::warn!("Reading server resp for {j:#?} error: {x:#?}"),
             },
             Err(x) => log::warn!("Sending data {j:#?} error: {x:#?}"),
         }
 
         // update last send
-        self.last_send = now;
+        self.last_send = Local::now();
     }
 }
 
@@ -236,20 +363,33 @@ pub fn count_total_xp() -> u32 {
     XPS.lock().unwrap().values().sum()
 }
 
+/// XP earned for the edit from `old_text` to `new_text`: the number of
+/// freshly inserted characters. Code::Stats counts characters typed, not
+/// change events, so a 500-char paste and a single keypress must not earn
+/// the same XP -- deletions (including the delete half of a replacement)
+/// don't count.
+fn xp_for_diff(old_text: &helix_core::Rope, new_text: &helix_core::Rope) -> u32 {
+    TextChange::from_diff(old_text, new_text)
+        .iter()
+        .map(|change| change.insert.chars().count() as u32)
+        .sum()
+}
+
 pub fn register_hooks(handlers: &Handlers) {
     log::info!("CodeStats hook registred");
 
     let tx = handlers.codestats.clone();
     register_hook!(move |event: &mut DocumentDidChange<'_>| {
-        // let old = &event.old_text;
-        // let new = &event.doc.text();
-        // log::info!("document changed: new is {0:#?} than old", new.cmp(old));
-
         let Some(language) = resolve_language(event.doc) else {
             return anyhow::Ok(());
         };
 
-        add_xp(language, 1);
+        let xp = xp_for_diff(event.old_text, event.doc.text());
+        if xp == 0 {
+            return anyhow::Ok(());
+        }
+
+        add_xp(language, xp);
 
         send_blocking_freezing(&tx, CodeStatsEvent::Update);
 
@@ -324,3 +464,75 @@ pub fn typeablecmd_send_info(
     cx.editor.handlers.trigger_codestats_send();
     anyhow::Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helix_core::Rope;
+
+    fn pulse(xps: &[(&str, u32)]) -> CodeStatsPulse {
+        CodeStatsPulse {
+            coded_at: "2024-01-01T00:00:00+00:00".to_string(),
+            xps: xps
+                .iter()
+                .map(|(language, xp)| CodeStatsPulseXP {
+                    language: language.to_string(),
+                    xp: *xp,
+                })
+                .collect(),
+        }
+    }
+
+    fn xp_of(pulse: &CodeStatsPulse, language: &str) -> Option<u32> {
+        pulse.xps.iter().find(|xp| xp.language == language).map(|xp| xp.xp)
+    }
+
+    #[test]
+    fn coalesce_sums_xp_per_language_across_pulses() {
+        let pulses = vec![
+            pulse(&[("Rust", 10), ("Lua", 3)]),
+            pulse(&[("Rust", 5)]),
+            pulse(&[("Lua", 2), ("Python", 7)]),
+        ];
+
+        let merged = coalesce(&pulses);
+        assert_eq!(xp_of(&merged, "Rust"), Some(15));
+        assert_eq!(xp_of(&merged, "Lua"), Some(5));
+        assert_eq!(xp_of(&merged, "Python"), Some(7));
+    }
+
+    #[test]
+    fn cap_pulses_drops_the_oldest_entries_past_the_limit() {
+        let mut pulses = vec![pulse(&[("Rust", 1)]), pulse(&[("Rust", 2)]), pulse(&[("Rust", 3)])];
+
+        cap_pulses(&mut pulses, 2);
+
+        assert_eq!(pulses.len(), 2);
+        assert_eq!(xp_of(&pulses[0], "Rust"), Some(2));
+        assert_eq!(xp_of(&pulses[1], "Rust"), Some(3));
+    }
+
+    #[test]
+    fn cap_pulses_is_a_no_op_under_the_limit() {
+        let mut pulses = vec![pulse(&[("Rust", 1)]), pulse(&[("Rust", 2)])];
+
+        cap_pulses(&mut pulses, 5);
+
+        assert_eq!(pulses.len(), 2);
+    }
+
+    #[test]
+    fn xp_for_diff_counts_inserted_chars_not_change_events() {
+        let keypress_xp = xp_for_diff(&Rope::from("ab"), &Rope::from("abc"));
+        let paste_xp = xp_for_diff(&Rope::from("ab"), &Rope::from("abcdefghij"));
+
+        assert_eq!(keypress_xp, 1);
+        assert_eq!(paste_xp, 8);
+        assert_ne!(keypress_xp, paste_xp);
+    }
+
+    #[test]
+    fn xp_for_diff_ignores_pure_deletions() {
+        assert_eq!(xp_for_diff(&Rope::from("abc"), &Rope::from("a")), 0);
+    }
+}
@@ -0,0 +1,221 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use helix_core::{Rope, Tendril, Transaction};
+use helix_view::events::DocumentDidChange;
+use helix_view::handlers::change::TextChange;
+use helix_view::handlers::collab::{CharId, CollabEvent, CollabOp, WootSequence};
+use helix_view::handlers::Handlers;
+use helix_view::{Document, DocumentId, ViewId};
+use once_cell::sync::Lazy;
+
+use crate::{compositor, ui::PromptEvent};
+use helix_event::{register_hook, send_blocking_freezing};
+
+/// Per-document WOOT state, kept on the main thread so local edits can be
+/// translated into ops (and remote ops integrated) right where the
+/// `Transaction` is available, the same way `codestats::XPS` is accumulated
+/// synchronously and only handed to the async handler for I/O.
+static DOCS: Lazy<Mutex<HashMap<DocumentId, WootSequence>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Edits produced by integrating remote ops, waiting to be applied to their
+/// document as a `Transaction` next time the editor polls
+/// [`apply_pending_remote_edits`].
+static PENDING_REMOTE_EDITS: Lazy<Mutex<HashMap<DocumentId, Vec<(usize, usize, Option<char>)>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// This site's id for the current process. Real peer negotiation (handing
+/// out stable, collision-free site ids when joining a session) is out of
+/// scope here; derive something unique enough for a single run.
+static SITE_ID: Lazy<u64> = Lazy::new(|| std::process::id() as u64);
+
+struct LocalClock(u64);
+
+static LOCAL_CLOCK: Mutex<LocalClock> = Mutex::new(LocalClock(0));
+
+fn next_char_id() -> CharId {
+    let mut clock = LOCAL_CLOCK.lock().unwrap();
+    let id = CharId {
+        site: *SITE_ID,
+        clock: clock.0,
+    };
+    clock.0 += 1;
+    id
+}
+
+/// `DocumentDidChange` only exposes `old_text` and the document's current
+/// text, not the changeset that produced the edit, so the best we can do
+/// here is guess the changed span via [`TextChange::from_diff`]. Once the
+/// hook exposes the actual changeset this should use
+/// [`TextChange::from_transaction`] instead.
+fn ops_from_diff(seq: &mut WootSequence, old_text: &Rope, new_text: &Rope) -> Vec<CollabOp> {
+    let mut ops = Vec::new();
+    // `TextChange` spans are against `old_text`; shift each one by what the
+    // changes before it have already done to `seq`.
+    let mut shift: isize = 0;
+    for change in TextChange::from_diff(old_text, new_text) {
+        let from = (change.from as isize + shift) as usize;
+        for _ in change.from..change.to {
+            if let Some(op) = seq.delete_local(from) {
+                ops.push(op);
+            }
+        }
+        let mut inserted = 0;
+        for (i, ch) in change.insert.chars().enumerate() {
+            let id = next_char_id();
+            ops.push(seq.insert_local(id, ch, from + i));
+            inserted += 1;
+        }
+        shift += inserted - (change.to - change.from) as isize;
+    }
+    ops
+}
+
+/// Pull out (and clear) the edits queued for `doc` by [`register_hooks`]'s
+/// `RemoteOps` handling. Split out from [`apply_pending_remote_edits`] so
+/// the queueing side of the pipeline can be exercised without a live
+/// `Document`.
+fn take_pending_remote_edits(doc: DocumentId) -> Vec<(usize, usize, Option<char>)> {
+    PENDING_REMOTE_EDITS.lock().unwrap().remove(&doc).unwrap_or_default()
+}
+
+/// Apply any edits queued by [`register_hooks`]'s `RemoteOps` handling to
+/// the live document. Reachable today via [`typeablecmd_collab_sync`]; an
+/// idle/render-tick call site that drains this automatically on every
+/// main-loop pass, the same way other handlers flush state accumulated off
+/// the main thread, needs a hook into the application event loop that
+/// doesn't exist in this crate yet.
+pub fn apply_pending_remote_edits(doc: &mut Document, view: ViewId) {
+    let edits = take_pending_remote_edits(doc.id());
+    if edits.is_empty() {
+        return;
+    }
+
+    // Each edit's offsets assume the edits before it have been applied, so
+    // apply them one at a time rather than as a single changeset.
+    for (from, to, insert) in edits {
+        let change = (from, to, insert.map(Tendril::from_char));
+        let transaction = Transaction::change(doc.text(), std::iter::once(change));
+        doc.apply(&transaction, view);
+    }
+}
+
+pub(super) struct CollabHandler;
+
+impl CollabHandler {
+    pub fn new() -> CollabHandler {
+        CollabHandler
+    }
+}
+
+impl helix_event::AsyncHook for CollabHandler {
+    type Event = CollabEvent;
+
+    fn handle_event(
+        &mut self,
+        event: Self::Event,
+        _timeout: Option<tokio::time::Instant>,
+    ) -> Option<tokio::time::Instant> {
+        match event {
+            CollabEvent::LocalChange { ops, .. } => {
+                // TODO(collab): hand `ops` to the network/transport layer
+                // once peer connections exist. For now this is the seam
+                // `trigger_collab_local_change` feeds.
+                log::debug!("collab: broadcasting {} local op(s)", ops.len());
+            }
+            CollabEvent::RemoteOps { doc, ops } => {
+                let mut docs = DOCS.lock().unwrap();
+                let seq = docs.entry(doc).or_default();
+                let mut edits = Vec::new();
+                for op in ops {
+                    edits.extend(seq.integrate_remote(op));
+                }
+                if !edits.is_empty() {
+                    PENDING_REMOTE_EDITS.lock().unwrap().entry(doc).or_default().extend(edits);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helix_event::AsyncHook;
+
+    // `DOCS`/`PENDING_REMOTE_EDITS` are process-global, and `DocumentId`
+    // isn't constructible outside helix-view other than via `default()`,
+    // so keep this as one test exercising the whole `RemoteOps` pipeline
+    // in sequence rather than several tests that could race on the same
+    // key if run in parallel.
+    #[test]
+    fn remote_ops_are_queued_and_drained_end_to_end() {
+        let doc = DocumentId::default();
+        let mut handler = CollabHandler::new();
+
+        let id = CharId { site: 7, clock: 0 };
+        let ops = vec![CollabOp::Insert {
+            id,
+            value: 'h',
+            prev: None,
+            next: None,
+        }];
+        handler.handle_event(CollabEvent::RemoteOps { doc, ops }, None);
+
+        let edits = take_pending_remote_edits(doc);
+        assert_eq!(edits, vec![(0, 0, Some('h'))]);
+
+        // Draining clears the queue until more ops arrive.
+        assert!(take_pending_remote_edits(doc).is_empty());
+
+        // A `LocalChange` event (broadcast-only) must never queue a remote
+        // edit to be applied back to the document.
+        handler.handle_event(
+            CollabEvent::LocalChange {
+                doc,
+                ops: vec![CollabOp::Insert {
+                    id: CharId { site: 1, clock: 0 },
+                    value: 'a',
+                    prev: None,
+                    next: None,
+                }],
+            },
+            None,
+        );
+        assert!(take_pending_remote_edits(doc).is_empty());
+    }
+}
+
+pub fn register_hooks(handlers: &Handlers) {
+    log::info!("collab hook registered");
+
+    let tx = handlers.collab.clone();
+    register_hook!(move |event: &mut DocumentDidChange<'_>| {
+        let doc_id = event.doc.id();
+        let mut docs = DOCS.lock().unwrap();
+        let seq = docs.entry(doc_id).or_default();
+        let ops = ops_from_diff(seq, event.old_text, event.doc.text());
+        drop(docs);
+
+        if !ops.is_empty() {
+            send_blocking_freezing(&tx, CollabEvent::LocalChange { doc: doc_id, ops });
+        }
+
+        anyhow::Ok(())
+    });
+}
+
+/// Flush any remote edits queued for the current document into it. Until
+/// the application event loop grows a drain point for this on every tick,
+/// this command is the only reachable way to apply them.
+pub fn typeablecmd_collab_sync(
+    cx: &mut compositor::Context,
+    _args: &[Cow<str>],
+    _event: PromptEvent,
+) -> anyhow::Result<()> {
+    let (view, doc) = current!(cx.editor);
+    apply_pending_remote_edits(doc, view.id);
+    anyhow::Ok(())
+}
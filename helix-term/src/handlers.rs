@@ -6,7 +6,7 @@ use helix_event::AsyncHook;
 use crate::config::Config;
 use crate::handlers::completion::CompletionHandler;
 use crate::handlers::signature_help::SignatureHelpHandler;
-use crate::{codestats, events};
+use crate::{codestats, collab, events};
 
 pub use completion::trigger_auto_completion;
 pub use helix_view::handlers::Handlers;
@@ -20,16 +20,24 @@ pub fn setup(config: Arc<ArcSwap<Config>>) -> Handlers {
     let completions = CompletionHandler::new(config.clone()).spawn();
     let signature_hints = SignatureHelpHandler::new().spawn();
     let codestats = codestats::CodeStatsHandler::new(config).spawn();
+    let collab = collab::CollabHandler::new().spawn();
 
     let handlers = Handlers {
         completions,
         signature_hints,
         codestats,
+        collab,
     };
 
+    // Replay any pulses left over from a previous session now that the
+    // handler's async task is running, instead of blocking startup on a
+    // network request from the constructor.
+    handlers.trigger_codestats_send();
+
     completion::register_hooks(&handlers);
     signature_help::register_hooks(&handlers);
     codestats::register_hooks(&handlers);
+    collab::register_hooks(&handlers);
 
     handlers
 }
@@ -2,9 +2,12 @@ use helix_event::{send_blocking};
 use tokio::sync::mpsc::Sender;
 
 use crate::codestats::CodeStatsEvent;
+use crate::handlers::collab::{CollabEvent, CollabOp};
 use crate::handlers::lsp::SignatureHelpInvoked;
 use crate::{DocumentId, Editor, ViewId};
 
+pub mod change;
+pub mod collab;
 pub mod dap;
 pub mod diagnostics;
 pub mod lsp;
@@ -21,6 +24,7 @@ pub struct Handlers {
     pub signature_hints: Sender<lsp::SignatureHelpEvent>,
     pub auto_save: Sender<AutoSaveEvent>,
     pub codestats: Sender<CodeStatsEvent>,
+    pub collab: Sender<CollabEvent>,
 }
 
 impl Handlers {
@@ -52,4 +56,21 @@ impl Handlers {
     pub fn trigger_codestats_send(&self) {
         send_blocking(&self.codestats, CodeStatsEvent::ForceSend);
     }
+
+    /// Broadcast locally made edits to `doc`, encoded as WOOT ops, to peers.
+    pub fn trigger_collab_local_change(&self, doc: DocumentId, ops: Vec<CollabOp>) {
+        if ops.is_empty() {
+            return;
+        }
+        send_blocking(&self.collab, CollabEvent::LocalChange { doc, ops });
+    }
+
+    /// Integrate WOOT ops received from a peer into `doc`. The real caller
+    /// is the network/transport layer that decodes ops off the wire; until
+    /// that exists, `:collab-sync` (`typeablecmd_collab_sync`) is the
+    /// reachable way to observe the result of a call to this on a live
+    /// document.
+    pub fn trigger_collab_remote_ops(&self, doc: DocumentId, ops: Vec<CollabOp>) {
+        send_blocking(&self.collab, CollabEvent::RemoteOps { doc, ops });
+    }
 }
@@ -0,0 +1,284 @@
+use helix_core::{Operation, Rope, Tendril, Transaction};
+use serde::{Deserialize, Serialize};
+
+/// A single edit to a buffer, expressed against the buffer's state
+/// *before* the edit: the span being replaced plus the text that replaces
+/// it. Inserts, deletes and replacements are all represented uniformly --
+/// an insert has an empty `from..to`, a delete has an empty `insert`.
+///
+/// `from_transaction` is the precise way to get these, for callers that
+/// already have the `Transaction` in hand; `from_diff` is a fallback for
+/// callers (like the `DocumentDidChange` hooks today) that only have the
+/// before/after text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextChange {
+    pub from: usize,
+    pub to: usize,
+    pub insert: Tendril,
+}
+
+impl TextChange {
+    /// Walk `transaction`'s changeset, yielding the minimal span+content
+    /// edits it applies. A delete and an insert at the same position (a
+    /// replacement, in either order in the changeset) are merged into one
+    /// change rather than reported as a delete and an insert.
+    pub fn from_transaction(transaction: &Transaction) -> Vec<TextChange> {
+        let mut changes = Vec::new();
+        let mut pos = 0;
+        let mut ops = transaction.changes().changes().iter().peekable();
+
+        while let Some(op) = ops.next() {
+            match op {
+                Operation::Retain(n) => pos += n,
+                Operation::Delete(n) => {
+                    let to = pos + n;
+                    let insert = match ops.peek() {
+                        Some(Operation::Insert(text)) => {
+                            let text = (*text).clone();
+                            ops.next();
+                            text
+                        }
+                        _ => Tendril::new(),
+                    };
+                    changes.push(TextChange { from: pos, to, insert });
+                    pos = to;
+                }
+                Operation::Insert(text) => {
+                    // Changesets store a replacement as an insert followed
+                    // by a delete at the same position.
+                    let to = match ops.peek() {
+                        Some(Operation::Delete(n)) => {
+                            let to = pos + n;
+                            ops.next();
+                            to
+                        }
+                        _ => pos,
+                    };
+                    changes.push(TextChange {
+                        from: pos,
+                        to,
+                        insert: text.clone(),
+                    });
+                    pos = to;
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// Derive the changes between `old_text` and `new_text` from an LCS
+    /// diff. Use this when only the before/after text is available and
+    /// not the changeset that produced it (e.g. `DocumentDidChange` hooks,
+    /// which only expose `old_text`).
+    ///
+    /// Trimming just the outer common prefix/suffix and reporting
+    /// everything in between as one replacement is tempting but wrong: two
+    /// independent single-char edits (`"aaaXaaaYaaa"` -> `"aaaxaaayaaa"`)
+    /// would be reported as one 5-character replacement, wildly
+    /// overcounting anything derived from the edit's size (e.g. XP). Diff
+    /// the trimmed middle properly so each independently-changed hunk
+    /// becomes its own `TextChange`.
+    pub fn from_diff(old_text: &Rope, new_text: &Rope) -> Vec<TextChange> {
+        let old_chars: Vec<char> = old_text.chars().collect();
+        let new_chars: Vec<char> = new_text.chars().collect();
+
+        let prefix = common_prefix_len(&old_chars, &new_chars);
+        let old_rest = &old_chars[prefix..];
+        let new_rest = &new_chars[prefix..];
+        let suffix = common_suffix_len(old_rest, new_rest);
+        let old_mid = &old_rest[..old_rest.len() - suffix];
+        let new_mid = &new_rest[..new_rest.len() - suffix];
+
+        if old_mid.is_empty() && new_mid.is_empty() {
+            return Vec::new();
+        }
+
+        // The LCS table below is O(n*m); guard against pathological
+        // multi-hunk edits blowing it up and fall back to reporting the
+        // whole middle as a single hunk rather than hanging.
+        const MAX_LCS_CELLS: usize = 4_000_000;
+        if old_mid.len().saturating_mul(new_mid.len()) > MAX_LCS_CELLS {
+            return vec![TextChange {
+                from: prefix,
+                to: prefix + old_mid.len(),
+                insert: new_mid.iter().collect(),
+            }];
+        }
+
+        diff_hunks(old_mid, new_mid, prefix)
+    }
+
+    /// Replay this change on `rope`, the inverse of how it was captured
+    /// from a transaction.
+    pub fn apply_to(&self, rope: &mut Rope) {
+        if self.to > self.from {
+            rope.remove(self.from..self.to);
+        }
+        if !self.insert.is_empty() {
+            rope.insert(self.from, &self.insert);
+        }
+    }
+}
+
+fn common_prefix_len(a: &[char], b: &[char]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count()
+}
+
+fn common_suffix_len(a: &[char], b: &[char]) -> usize {
+    a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count()
+}
+
+/// Diff `old`/`new` via an LCS table and split the result into one
+/// `TextChange` per independently-changed hunk (rather than one
+/// replacement spanning the whole input). `base` is how far `old`/`new`
+/// sit into the original buffer, so the reported spans line up with it.
+fn diff_hunks(old: &[char], new: &[char], base: usize) -> Vec<TextChange> {
+    let n = old.len();
+    let m = new.len();
+
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..].
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    enum Run {
+        Equal,
+        Changed,
+    }
+
+    // (kind, old_from, old_to, new_from, new_to), built by walking the LCS
+    // table and merging consecutive same-kind steps into one run.
+    let mut runs: Vec<(Run, usize, usize, usize, usize)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            match runs.last_mut() {
+                Some((Run::Equal, _, old_to, _, new_to)) => {
+                    *old_to += 1;
+                    *new_to += 1;
+                }
+                _ => runs.push((Run::Equal, i, i + 1, j, j + 1)),
+            }
+            i += 1;
+            j += 1;
+        } else if j < m && (i >= n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            match runs.last_mut() {
+                Some((Run::Changed, _, old_to, _, new_to)) if *old_to == i => *new_to += 1,
+                _ => runs.push((Run::Changed, i, i, j, j + 1)),
+            }
+            j += 1;
+        } else {
+            match runs.last_mut() {
+                Some((Run::Changed, _, old_to, _, new_to)) if *new_to == j => *old_to += 1,
+                _ => runs.push((Run::Changed, i, i + 1, j, j)),
+            }
+            i += 1;
+        }
+    }
+
+    runs.into_iter()
+        .filter(|(kind, ..)| matches!(kind, Run::Changed))
+        .map(|(_, old_from, old_to, new_from, new_to)| TextChange {
+            from: base + old_from,
+            to: base + old_to,
+            insert: new[new_from..new_to].iter().collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_transaction_reports_a_plain_insert() {
+        let rope = Rope::from("ab");
+        let transaction = Transaction::change(&rope, [(2, 2, Some(Tendril::from("X")))].into_iter());
+
+        let changes = TextChange::from_transaction(&transaction);
+        assert_eq!(
+            changes,
+            vec![TextChange {
+                from: 2,
+                to: 2,
+                insert: Tendril::from("X"),
+            }]
+        );
+    }
+
+    #[test]
+    fn from_transaction_merges_a_delete_and_insert_into_one_replacement() {
+        let rope = Rope::from("ab");
+        let transaction = Transaction::change(&rope, [(1, 2, Some(Tendril::from("Z")))].into_iter());
+
+        let changes = TextChange::from_transaction(&transaction);
+        assert_eq!(
+            changes,
+            vec![TextChange {
+                from: 1,
+                to: 2,
+                insert: Tendril::from("Z"),
+            }]
+        );
+    }
+
+    #[test]
+    fn apply_to_replays_a_replacement() {
+        let mut rope = Rope::from("hello");
+        let change = TextChange {
+            from: 1,
+            to: 3,
+            insert: Tendril::from("ELL"),
+        };
+
+        change.apply_to(&mut rope);
+        assert_eq!(rope.to_string(), "hELLlo");
+    }
+
+    #[test]
+    fn from_diff_finds_the_single_changed_span() {
+        let old = Rope::from("aaaXaaa");
+        let new = Rope::from("aaaxaaa");
+
+        let changes = TextChange::from_diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![TextChange {
+                from: 3,
+                to: 4,
+                insert: Tendril::from("x"),
+            }]
+        );
+    }
+
+    #[test]
+    fn from_diff_splits_independent_edits_into_separate_hunks() {
+        let old = Rope::from("aaaaXaaaaYaaaa");
+        let new = Rope::from("aaaaxaaaayaaaa");
+
+        let changes = TextChange::from_diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                TextChange {
+                    from: 4,
+                    to: 5,
+                    insert: Tendril::from("x"),
+                },
+                TextChange {
+                    from: 9,
+                    to: 10,
+                    insert: Tendril::from("y"),
+                },
+            ]
+        );
+    }
+}
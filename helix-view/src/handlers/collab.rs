@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+
+use crate::DocumentId;
+
+/// Globally unique id of a single character inserted into a WOOT sequence:
+/// the site that created it plus that site's logical clock at the time of
+/// insertion. Ordering is deterministic across sites and is used to break
+/// ties between concurrent insertions at the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CharId {
+    pub site: u64,
+    pub clock: u64,
+}
+
+/// A single WOOT operation as broadcast to peers. Insertions carry the ids
+/// of the characters immediately before and after the insertion point so a
+/// remote site can place the character without a central server; deletions
+/// only need the id of the character being tombstoned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollabOp {
+    Insert {
+        id: CharId,
+        value: char,
+        prev: Option<CharId>,
+        next: Option<CharId>,
+    },
+    Delete {
+        id: CharId,
+    },
+}
+
+/// A character in a WOOT sequence. Deleted characters are never physically
+/// removed: they are tombstoned (`visible = false`) so later-arriving
+/// insertions that reference them as a neighbor still resolve.
+#[derive(Debug, Clone, Copy)]
+struct WootChar {
+    id: CharId,
+    value: char,
+    visible: bool,
+}
+
+/// A document modelled as a WOOT sequence CRDT, plus the bookkeeping needed
+/// to translate between CRDT positions and rope char offsets and to defer
+/// operations whose causal predecessors haven't arrived yet.
+#[derive(Debug, Default, Clone)]
+pub struct WootSequence {
+    chars: Vec<WootChar>,
+    /// Operations buffered because they reference a neighbor id we haven't
+    /// integrated yet, keyed by the missing id. Drained as soon as that id
+    /// is integrated.
+    pending: HashMap<CharId, Vec<CollabOp>>,
+}
+
+impl WootSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index_of(&self, id: CharId) -> Option<usize> {
+        self.chars.iter().position(|c| c.id == id)
+    }
+
+    /// Rope char offset of the character at `index` (or where it would sit
+    /// if it is tombstoned): the number of visible characters before it.
+    fn visible_offset_before(&self, index: usize) -> usize {
+        self.chars[..index].iter().filter(|c| c.visible).count()
+    }
+
+    /// Rope char offset that corresponds to inserting at the given CRDT
+    /// position (an index into the sequence, tombstones included).
+    pub fn rope_offset_of_position(&self, pos: usize) -> usize {
+        self.visible_offset_before(pos.min(self.chars.len()))
+    }
+
+    /// Ids of the visible characters on either side of rope offset
+    /// `offset` (the one before it and the one currently at it), i.e. the
+    /// ids that should be carried by an `Insert` op placed at that offset.
+    fn neighbors_at_offset(&self, offset: usize) -> (Option<CharId>, Option<CharId>) {
+        let mut seen = 0;
+        let mut prev = None;
+        for c in &self.chars {
+            if !c.visible {
+                continue;
+            }
+            if seen == offset {
+                return (prev, Some(c.id));
+            }
+            prev = Some(c.id);
+            seen += 1;
+        }
+        (prev, None)
+    }
+
+    /// Locate the character at visible rope offset `offset`, if any.
+    fn id_at_offset(&self, offset: usize) -> Option<CharId> {
+        self.chars.iter().filter(|c| c.visible).nth(offset).map(|c| c.id)
+    }
+
+    /// Generate the op for inserting `value` at rope offset `offset`,
+    /// integrating it locally so the next call sees it as a neighbor.
+    pub fn insert_local(&mut self, id: CharId, value: char, offset: usize) -> CollabOp {
+        let (prev, next) = self.neighbors_at_offset(offset);
+        self.place(id, value, prev, next);
+        CollabOp::Insert { id, value, prev, next }
+    }
+
+    /// Generate the op for deleting the character at rope offset `offset`.
+    /// Returns `None` if there is nothing visible at that offset.
+    pub fn delete_local(&mut self, offset: usize) -> Option<CollabOp> {
+        let id = self.id_at_offset(offset)?;
+        self.tombstone(id);
+        Some(CollabOp::Delete { id })
+    }
+
+    /// Integrate a remote op, recursively resolving any buffered ops that
+    /// were waiting on it. Returns the rope-offset edits that resulted,
+    /// in application order, so the caller can build a `Transaction`.
+    ///
+    /// Ops whose neighbors haven't been seen yet are buffered rather than
+    /// dropped, since later-arriving ops may resolve the causal gap.
+    pub fn integrate_remote(&mut self, op: CollabOp) -> Vec<(usize, usize, Option<char>)> {
+        let mut edits = Vec::new();
+        self.integrate_one(op, &mut edits);
+        edits
+    }
+
+    fn integrate_one(&mut self, op: CollabOp, edits: &mut Vec<(usize, usize, Option<char>)>) {
+        match op {
+            CollabOp::Insert { id, value, prev, next } => {
+                if self.index_of(id).is_some() {
+                    // Already integrated (duplicate delivery); ignore.
+                    return;
+                }
+                if !self.neighbors_known(prev, next) {
+                    self.buffer(prev, next, CollabOp::Insert { id, value, prev, next });
+                    return;
+                }
+                let offset = self.place(id, value, prev, next);
+                edits.push((offset, offset, Some(value)));
+            }
+            CollabOp::Delete { id } => {
+                let Some(index) = self.index_of(id) else {
+                    self.buffer(Some(id), None, CollabOp::Delete { id });
+                    return;
+                };
+                if self.chars[index].visible {
+                    let offset = self.visible_offset_before(index);
+                    self.chars[index].visible = false;
+                    edits.push((offset, offset + 1, None));
+                }
+            }
+        }
+        self.drain_pending(op_id(&op), edits);
+    }
+
+    fn neighbors_known(&self, prev: Option<CharId>, next: Option<CharId>) -> bool {
+        prev.is_none_or(|id| self.index_of(id).is_some())
+            && next.is_none_or(|id| self.index_of(id).is_some())
+    }
+
+    fn buffer(&mut self, prev: Option<CharId>, next: Option<CharId>, op: CollabOp) {
+        let missing = [prev, next].into_iter().flatten().find(|id| self.index_of(*id).is_none());
+        let key = missing.unwrap_or_else(|| match op {
+            CollabOp::Delete { id } => id,
+            _ => unreachable!("an insert missing a neighbor always has a `missing` id"),
+        });
+        self.pending.entry(key).or_default().push(op);
+    }
+
+    fn drain_pending(&mut self, resolved: CharId, edits: &mut Vec<(usize, usize, Option<char>)>) {
+        if let Some(waiting) = self.pending.remove(&resolved) {
+            for op in waiting {
+                self.integrate_one(op, edits);
+            }
+        }
+    }
+
+    /// Insert `id` between `prev` and `next`, breaking ties among any
+    /// characters already present in that gap (concurrent insertions) by
+    /// keeping the gap sorted on `CharId`. Returns the resulting visible
+    /// rope offset.
+    fn place(&mut self, id: CharId, value: char, prev: Option<CharId>, next: Option<CharId>) -> usize {
+        let lower = prev.map_or(0, |p| self.index_of(p).map_or(0, |i| i + 1));
+        let upper = next.map_or(self.chars.len(), |n| self.index_of(n).unwrap_or(self.chars.len()));
+        let mut at = lower;
+        while at < upper.min(self.chars.len()) && self.chars[at].id < id {
+            at += 1;
+        }
+        let offset = self.visible_offset_before(at);
+        self.chars.insert(at, WootChar { id, value, visible: true });
+        offset
+    }
+
+    fn tombstone(&mut self, id: CharId) {
+        if let Some(i) = self.index_of(id) {
+            self.chars[i].visible = false;
+        }
+    }
+}
+
+fn op_id(op: &CollabOp) -> CharId {
+    match *op {
+        CollabOp::Insert { id, .. } => id,
+        CollabOp::Delete { id } => id,
+    }
+}
+
+/// Events understood by the collaborative editing handler.
+#[derive(Debug)]
+pub enum CollabEvent {
+    /// Local edits to `doc`, already encoded as WOOT ops, to be broadcast
+    /// to peers.
+    LocalChange { doc: DocumentId, ops: Vec<CollabOp> },
+    /// Ops received from a peer that must be integrated into `doc`'s WOOT
+    /// sequence and applied to the buffer as a `Transaction`.
+    RemoteOps { doc: DocumentId, ops: Vec<CollabOp> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visible_string(seq: &WootSequence) -> String {
+        seq.chars.iter().filter(|c| c.visible).map(|c| c.value).collect()
+    }
+
+    #[test]
+    fn local_insert_and_delete_roundtrip() {
+        let mut seq = WootSequence::new();
+        seq.insert_local(CharId { site: 1, clock: 0 }, 'a', 0);
+        seq.insert_local(CharId { site: 1, clock: 1 }, 'b', 1);
+        seq.insert_local(CharId { site: 1, clock: 2 }, 'c', 2);
+        assert_eq!(visible_string(&seq), "abc");
+
+        seq.delete_local(1);
+        assert_eq!(visible_string(&seq), "ac");
+    }
+
+    #[test]
+    fn concurrent_inserts_converge_regardless_of_integration_order() {
+        // Both sites start from the same base sequence "ac".
+        let mut base = WootSequence::new();
+        let op_a = base.insert_local(CharId { site: 1, clock: 0 }, 'a', 0);
+        let op_c = base.insert_local(CharId { site: 1, clock: 1 }, 'c', 1);
+        let (prev, next) = (Some(op_id(&op_a)), Some(op_id(&op_c)));
+
+        // Two peers concurrently insert between `a` and `c`, neither aware
+        // of the other's edit.
+        let op_x = CollabOp::Insert {
+            id: CharId { site: 2, clock: 0 },
+            value: 'x',
+            prev,
+            next,
+        };
+        let op_y = CollabOp::Insert {
+            id: CharId { site: 3, clock: 0 },
+            value: 'y',
+            prev,
+            next,
+        };
+
+        let mut applied_x_then_y = base.clone();
+        applied_x_then_y.integrate_remote(op_x.clone());
+        applied_x_then_y.integrate_remote(op_y.clone());
+
+        let mut applied_y_then_x = base.clone();
+        applied_y_then_x.integrate_remote(op_y);
+        applied_y_then_x.integrate_remote(op_x);
+
+        assert_eq!(visible_string(&applied_x_then_y), visible_string(&applied_y_then_x));
+    }
+
+    #[test]
+    fn insert_referencing_unseen_predecessor_is_buffered_until_it_arrives() {
+        let mut seq = WootSequence::new();
+        let id_a = CharId { site: 1, clock: 0 };
+        let id_b = CharId { site: 1, clock: 1 };
+
+        // `b`'s op references `a` as its left neighbor, but `a` hasn't been
+        // integrated yet: it must be buffered, not dropped or misplaced.
+        let op_b = CollabOp::Insert {
+            id: id_b,
+            value: 'b',
+            prev: Some(id_a),
+            next: None,
+        };
+        let edits = seq.integrate_remote(op_b);
+        assert!(edits.is_empty());
+        assert_eq!(visible_string(&seq), "");
+
+        // `a` was created before `b` existed, so its op can't name `b`.
+        let op_a = CollabOp::Insert {
+            id: id_a,
+            value: 'a',
+            prev: None,
+            next: None,
+        };
+        seq.integrate_remote(op_a);
+        assert_eq!(visible_string(&seq), "ab");
+    }
+
+    #[test]
+    fn insert_anchored_on_tombstoned_char_still_resolves() {
+        let mut seq = WootSequence::new();
+        let op_a = seq.insert_local(CharId { site: 1, clock: 0 }, 'a', 0);
+        let id_a = op_id(&op_a);
+
+        // Remote deletes `a` before we see an insert that uses it as a
+        // neighbor.
+        seq.integrate_remote(CollabOp::Delete { id: id_a });
+        assert_eq!(visible_string(&seq), "");
+
+        // A later insert anchored after the (now tombstoned) `a` must
+        // still resolve using its position in the underlying sequence.
+        let id_b = CharId { site: 2, clock: 0 };
+        let edits = seq.integrate_remote(CollabOp::Insert {
+            id: id_b,
+            value: 'b',
+            prev: Some(id_a),
+            next: None,
+        });
+        assert_eq!(edits, vec![(0, 0, Some('b'))]);
+        assert_eq!(visible_string(&seq), "b");
+    }
+}